@@ -0,0 +1,145 @@
+use std::path::{Path, PathBuf};
+use anyhow::{Context, Result};
+use image::{imageops::FilterType, DynamicImage, GenericImageView, Rgba, RgbaImage};
+
+/// 72 DPI is the reference resolution a `background.png` is authored at;
+/// the `@2x` variant doubles every pixel dimension for Retina displays.
+const RETINA_SCALE: u32 = 2;
+
+/// The files [`prepare_background`] wrote into `.background`, and which one
+/// Finder's window background should actually be set to.
+pub struct BackgroundPaths {
+    /// Plain, non-Retina `background.png`.
+    pub base: PathBuf,
+    /// Sharper `background@2x.png`, when a high-res source was available.
+    pub retina: Option<PathBuf>,
+}
+
+impl BackgroundPaths {
+    /// The file the `.DS_Store` `.background` alias should point at:
+    /// the Retina variant when one was produced, since Finder picks up the
+    /// `@2x` sibling from the aliased file by filename convention, falling
+    /// back to the base file otherwise.
+    pub fn alias_target(&self) -> &Path {
+        self.retina.as_deref().unwrap_or(&self.base)
+    }
+}
+
+/// Normalizes `source` to the DMG window size and writes the result into
+/// `dest_dir`.
+///
+/// Always writes a plain `background.png` scaled/letterboxed to
+/// `window_size`. A Retina `background@2x.png` is also written alongside it
+/// whenever a sharper source is available — either a `@2x` sibling file, or
+/// `source` itself already being at least twice `window_size` (the common
+/// single-asset workflow: author one big image and let the tool downsample
+/// it for both variants). Use [`BackgroundPaths::alias_target`] to pick the
+/// file the `.DS_Store` alias should reference.
+pub fn prepare_background(source: &Path, window_size: (u32, u32), dest_dir: &Path) -> Result<BackgroundPaths> {
+    let decoded = image::open(source)
+        .with_context(|| format!("failed to load background image {:?}", source))?;
+
+    let png_path = dest_dir.join("background.png");
+    fit_to_canvas(&decoded, window_size, 1).save(&png_path)
+        .with_context(|| format!("failed to write {:?}", png_path))?;
+
+    let hi_res_source = match find_retina_sibling(source) {
+        Some(hi_res_path) => Some(
+            image::open(&hi_res_path)
+                .with_context(|| format!("failed to load retina background {:?}", hi_res_path))?,
+        ),
+        None if is_high_res(&decoded, window_size) => Some(decoded),
+        None => None,
+    };
+
+    let retina_path = if let Some(hi_res_source) = hi_res_source {
+        let retina_path = dest_dir.join("background@2x.png");
+        fit_to_canvas(&hi_res_source, window_size, RETINA_SCALE).save(&retina_path)
+            .with_context(|| format!("failed to write {:?}", retina_path))?;
+        Some(retina_path)
+    } else {
+        None
+    };
+
+    Ok(BackgroundPaths { base: png_path, retina: retina_path })
+}
+
+/// Looks for a `<stem>@2x.<ext>` sibling next to `source`.
+fn find_retina_sibling(source: &Path) -> Option<PathBuf> {
+    let stem = source.file_stem()?.to_str()?;
+    let ext = source.extension()?.to_str()?;
+    let candidate = source.with_file_name(format!("{}@2x.{}", stem, ext));
+    candidate.exists().then_some(candidate)
+}
+
+/// True when `decoded` is already at least Retina-scale relative to
+/// `window_size`, so it can serve as its own `@2x` source without an
+/// explicit sibling file.
+fn is_high_res(decoded: &DynamicImage, window_size: (u32, u32)) -> bool {
+    let (w, h) = decoded.dimensions();
+    w >= window_size.0 * RETINA_SCALE && h >= window_size.1 * RETINA_SCALE
+}
+
+/// Scales `decoded` and centers it onto a canvas of `window_size * scale`,
+/// padding with transparent pixels if the aspect ratios don't match. Split
+/// out from [`prepare_background`] so the letterbox math is testable without
+/// file I/O.
+fn fit_to_canvas(decoded: &DynamicImage, window_size: (u32, u32), scale: u32) -> DynamicImage {
+    let (target_w, target_h) = (window_size.0 * scale, window_size.1 * scale);
+
+    let (src_w, src_h) = decoded.dimensions();
+    let src_ratio = src_w as f64 / src_h as f64;
+    let target_ratio = target_w as f64 / target_h as f64;
+    let (fit_w, fit_h) = if src_ratio > target_ratio {
+        (target_w, (target_w as f64 / src_ratio).round() as u32)
+    } else {
+        ((target_h as f64 * src_ratio).round() as u32, target_h)
+    };
+    let resized = decoded.resize_exact(fit_w.max(1), fit_h.max(1), FilterType::Lanczos3);
+
+    let mut canvas = RgbaImage::from_pixel(target_w, target_h, Rgba([0, 0, 0, 0]));
+    let x_off = (target_w.saturating_sub(fit_w)) / 2;
+    let y_off = (target_h.saturating_sub(fit_h)) / 2;
+    image::imageops::overlay(&mut canvas, &resized.to_rgba8(), x_off as i64, y_off as i64);
+
+    DynamicImage::ImageRgba8(canvas)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn solid(w: u32, h: u32) -> DynamicImage {
+        DynamicImage::ImageRgba8(RgbaImage::from_pixel(w, h, Rgba([255, 0, 0, 255])))
+    }
+
+    #[test]
+    fn fit_to_canvas_matches_target_dimensions() {
+        let fitted = fit_to_canvas(&solid(100, 100), (540, 380), 1);
+        assert_eq!(fitted.dimensions(), (540, 380));
+    }
+
+    #[test]
+    fn fit_to_canvas_scales_retina_by_scale_factor() {
+        let fitted = fit_to_canvas(&solid(540, 380), (540, 380), 2);
+        assert_eq!(fitted.dimensions(), (1080, 760));
+    }
+
+    #[test]
+    fn fit_to_canvas_letterboxes_wide_source() {
+        // A source wider than the window must be capped to the window's
+        // width and centered vertically, leaving padding top and bottom.
+        let fitted = fit_to_canvas(&solid(1000, 100), (400, 400), 1);
+        assert_eq!(fitted.dimensions(), (400, 400));
+        assert_eq!(fitted.get_pixel(0, 0), Rgba([0, 0, 0, 0]));
+        assert_eq!(fitted.get_pixel(200, 200), Rgba([255, 0, 0, 255]));
+    }
+
+    #[test]
+    fn is_high_res_detects_source_at_least_double_window_size() {
+        assert!(is_high_res(&solid(1080, 760), (540, 380)));
+        assert!(!is_high_res(&solid(540, 380), (540, 380)));
+        // Wide enough but not tall enough still doesn't count as Retina.
+        assert!(!is_high_res(&solid(1080, 500), (540, 380)));
+    }
+}
@@ -0,0 +1,182 @@
+use std::path::{Path, PathBuf};
+use anyhow::{Context, Result};
+use sha2::{Digest, Sha256};
+use tokio::io::AsyncReadExt;
+use tokio::sync::Semaphore;
+use tokio::task::JoinSet;
+
+/// Chunk size used when streaming a file through the verification hasher,
+/// so verifying large `.app` bundles doesn't double their peak memory use.
+const HASH_CHUNK_SIZE: usize = 64 * 1024;
+
+/// Recursively copies `src` onto `dest` using up to `workers` concurrent
+/// `tokio` tasks, preserving symlinks and executable bits. When `verify` is
+/// set, each copied file's contents are hashed and compared against the
+/// source, catching truncated/partial copies that a bare `cp` exit code
+/// can miss.
+pub async fn copy_tree_parallel(src: &Path, dest: &Path, workers: usize, verify: bool) -> Result<()> {
+    let semaphore = std::sync::Arc::new(Semaphore::new(workers.max(1)));
+    let mut tasks = JoinSet::new();
+    spawn_copy_tasks(src.to_path_buf(), dest.to_path_buf(), &semaphore, verify, &mut tasks).await?;
+
+    while let Some(result) = tasks.join_next().await {
+        result.context("copy task panicked")??;
+    }
+    Ok(())
+}
+
+/// Walks `src` depth-first, creating directories inline (cheap, and needed
+/// before child tasks can write into them) and spawning one task per file
+/// or symlink onto `tasks`.
+async fn spawn_copy_tasks(
+    src: PathBuf,
+    dest: PathBuf,
+    semaphore: &std::sync::Arc<Semaphore>,
+    verify: bool,
+    tasks: &mut JoinSet<Result<()>>,
+) -> Result<()> {
+    let file_type = tokio::fs::symlink_metadata(&src).await?.file_type();
+
+    if file_type.is_symlink() {
+        let target = tokio::fs::read_link(&src).await?;
+        let permit = semaphore.clone();
+        tasks.spawn(async move {
+            let _permit = permit.acquire_owned().await?;
+            let dest_for_err = dest.clone();
+            tokio::fs::symlink(&target, &dest).await
+                .with_context(|| format!("failed to create symlink {:?} -> {:?}", dest_for_err, target))
+        });
+    } else if file_type.is_dir() {
+        tokio::fs::create_dir_all(&dest).await?;
+        let mut entries = tokio::fs::read_dir(&src).await?;
+        while let Some(entry) = entries.next_entry().await? {
+            let child_name = entry.file_name();
+            Box::pin(spawn_copy_tasks(
+                src.join(&child_name),
+                dest.join(&child_name),
+                semaphore,
+                verify,
+                tasks,
+            ))
+            .await?;
+        }
+    } else {
+        let permit = semaphore.clone();
+        tasks.spawn(async move {
+            let _permit = permit.acquire_owned().await?;
+            copy_file(&src, &dest, verify).await
+        });
+    }
+
+    Ok(())
+}
+
+/// Copies a single file, preserving the executable bit on Unix, then
+/// optionally hashes both sides to confirm the copy is byte-for-byte.
+async fn copy_file(src: &Path, dest: &Path, verify: bool) -> Result<()> {
+    tokio::fs::copy(src, dest).await
+        .with_context(|| format!("failed to copy {:?} to {:?}", src, dest))?;
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let perms = tokio::fs::metadata(src).await?.permissions();
+        tokio::fs::set_permissions(dest, std::fs::Permissions::from_mode(perms.mode())).await?;
+    }
+
+    if verify {
+        let (src_hash, dest_hash) = tokio::try_join!(hash_file(src), hash_file(dest))?;
+        if src_hash != dest_hash {
+            anyhow::bail!("copy verification failed for {:?}: hash mismatch", dest);
+        }
+    }
+
+    Ok(())
+}
+
+/// Hashes `path` in fixed-size chunks rather than buffering the whole file,
+/// so verifying a large `.app` bundle doesn't double its peak memory use.
+async fn hash_file(path: &Path) -> Result<[u8; 32]> {
+    let mut file = tokio::fs::File::open(path).await
+        .with_context(|| format!("failed to open {:?} for hashing", path))?;
+    let mut hasher = Sha256::new();
+    let mut buf = vec![0u8; HASH_CHUNK_SIZE];
+    loop {
+        let n = file.read(&mut buf).await
+            .with_context(|| format!("failed to read {:?} for hashing", path))?;
+        if n == 0 {
+            break;
+        }
+        hasher.update(&buf[..n]);
+    }
+    Ok(hasher.finalize().into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Creates a fresh, empty scratch directory under the OS temp dir.
+    fn scratch_dir(label: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!(
+            "appdmg_copy_test_{}_{}_{}",
+            label,
+            std::process::id(),
+            std::thread::current().name().unwrap_or("t").replace("::", "_"),
+        ));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[tokio::test]
+    async fn copies_nested_directory_tree_with_verification() {
+        let root = scratch_dir("nested");
+        let src = root.join("src");
+        std::fs::create_dir_all(src.join("sub")).unwrap();
+        std::fs::write(src.join("a.txt"), b"hello").unwrap();
+        std::fs::write(src.join("sub/b.txt"), b"world").unwrap();
+        let dest = root.join("dest");
+
+        copy_tree_parallel(&src, &dest, 2, true).await.unwrap();
+
+        assert_eq!(std::fs::read(dest.join("a.txt")).unwrap(), b"hello");
+        assert_eq!(std::fs::read(dest.join("sub/b.txt")).unwrap(), b"world");
+        let _ = std::fs::remove_dir_all(&root);
+    }
+
+    #[tokio::test]
+    #[cfg(unix)]
+    async fn propagates_symlink_creation_errors_instead_of_swallowing_them() {
+        let root = scratch_dir("symlink-err");
+        let src = root.join("src");
+        std::fs::create_dir_all(&src).unwrap();
+        std::os::unix::fs::symlink(root.join("does-not-exist"), src.join("link")).unwrap();
+
+        let dest = root.join("dest");
+        std::fs::create_dir_all(&dest).unwrap();
+        // Pre-create a directory at the destination symlink path so
+        // `tokio::fs::symlink` fails instead of silently succeeding.
+        std::fs::create_dir_all(dest.join("link")).unwrap();
+
+        let result = copy_tree_parallel(&src, &dest, 2, false).await;
+        assert!(result.is_err(), "expected symlink collision to surface as an error");
+        let _ = std::fs::remove_dir_all(&root);
+    }
+
+    #[tokio::test]
+    async fn hash_file_matches_for_identical_content_and_differs_otherwise() {
+        let root = scratch_dir("hash");
+        std::fs::write(root.join("a.txt"), b"same content").unwrap();
+        std::fs::write(root.join("b.txt"), b"same content").unwrap();
+        std::fs::write(root.join("c.txt"), b"different content").unwrap();
+
+        let hash_a = hash_file(&root.join("a.txt")).await.unwrap();
+        let hash_b = hash_file(&root.join("b.txt")).await.unwrap();
+        let hash_c = hash_file(&root.join("c.txt")).await.unwrap();
+
+        assert_eq!(hash_a, hash_b);
+        assert_ne!(hash_a, hash_c);
+        let _ = std::fs::remove_dir_all(&root);
+    }
+}
@@ -0,0 +1,52 @@
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use anyhow::{anyhow, Context, Result};
+use image::imageops::FilterType;
+
+/// The base iconset sizes `iconutil` expects; each also gets an `@2x` entry
+/// at double the resolution. 64px has no base slot of its own — it only
+/// ever appears as `icon_32x32@2x.png`.
+const ICON_SIZES: &[u32] = &[16, 32, 128, 256, 512];
+
+/// Returns a path to an `.icns` file representing `source`.
+///
+/// If `source` is already an `.icns`, it is returned unchanged. Otherwise
+/// `source` is treated as a raster image: it's downscaled into a temporary
+/// `.iconset` directory at the sizes `iconutil` requires (including `@2x`
+/// variants) and assembled into an `.icns` under `temp_dir`.
+pub fn ensure_icns(source: &Path, temp_dir: &Path) -> Result<PathBuf> {
+    if source.extension().and_then(|e| e.to_str()) == Some("icns") {
+        return Ok(source.to_path_buf());
+    }
+
+    let image = image::open(source)
+        .with_context(|| format!("failed to load icon source {:?}", source))?;
+
+    let iconset_dir = temp_dir.join("VolumeIcon.iconset");
+    if iconset_dir.exists() {
+        std::fs::remove_dir_all(&iconset_dir)?;
+    }
+    std::fs::create_dir_all(&iconset_dir)?;
+
+    for &size in ICON_SIZES {
+        for (suffix, px) in [("", size), ("@2x", size * 2)] {
+            let resized = image.resize_exact(px, px, FilterType::Lanczos3);
+            let name = format!("icon_{0}x{0}{1}.png", size, suffix);
+            resized.save(iconset_dir.join(name))?;
+        }
+    }
+
+    let icns_path = temp_dir.join("VolumeIcon.icns");
+    let status = Command::new("iconutil")
+        .arg("-c").arg("icns")
+        .arg(&iconset_dir)
+        .arg("-o").arg(&icns_path)
+        .status()
+        .context("failed to run iconutil")?;
+    if !status.success() {
+        return Err(anyhow!("iconutil failed to assemble {:?}", icns_path));
+    }
+
+    let _ = std::fs::remove_dir_all(&iconset_dir);
+    Ok(icns_path)
+}
@@ -1,12 +1,15 @@
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::process::Command;
-use anyhow::{Result};
+use anyhow::{Context, Result};
 use serde::{Serialize, Deserialize};
 use tokio::fs;
 
 // Declare submodules
+pub mod background;
+pub mod copy;
 pub mod ds_store;
 pub mod ds_store_template;
+pub mod icon;
 pub mod macos_alias;
 
 use ds_store::{Entry, write_ds_store};
@@ -33,20 +36,168 @@ pub struct DmgWindowSize {
     pub height: u32,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+impl Default for DmgWindowSize {
+    fn default() -> Self {
+        DmgWindowSize { width: 540, height: 380 }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Default)]
 pub struct DmgWindow {
+    #[serde(default)]
     pub size: DmgWindowSize,
 }
 
+/// Default `icon-size` (in points) used when a spec file omits it.
+fn default_icon_size() -> f64 {
+    128.0
+}
+
 #[derive(Serialize, Deserialize, Debug)]
 pub struct DmgConfig {
     pub title: String,
     pub icon: String,
     pub background: String,
-    #[serde(rename = "icon-size")]
+    #[serde(rename = "icon-size", default = "default_icon_size")]
     pub icon_size: f64,
+    #[serde(default)]
     pub window: DmgWindow,
     pub contents: Vec<DmgContent>,
+    #[serde(default)]
+    pub compression: DmgCompression,
+    /// Number of concurrent tasks used to copy content into the staging
+    /// directory. Defaults to the CPU count when omitted.
+    #[serde(rename = "copy-workers", default)]
+    pub copy_workers: Option<usize>,
+    /// Whether to hash-verify each copied file against its source.
+    /// Defaults to `true`; large bundles that are already trusted can
+    /// disable this to skip hashing every file twice.
+    #[serde(rename = "verify-copies", default = "default_verify_copies")]
+    pub verify_copies: bool,
+}
+
+fn default_verify_copies() -> bool {
+    true
+}
+
+/// Compression format/level used by the final `hdiutil convert` pass.
+///
+/// Mirrors the formats `hdiutil` accepts for `-format`: `Zlib` maps to
+/// `UDZO` with an `-imagekey zlib-level=<n>` hint, the others map to their
+/// respective single-flag formats. `Lzfse` and `Lzma` produce smaller images
+/// but require macOS 10.11+ and 10.15+ respectively to mount.
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(tag = "format", rename_all = "lowercase")]
+pub enum DmgCompression {
+    Zlib { level: u8 },
+    Bzip2,
+    Lzfse,
+    Lzma,
+    None,
+}
+
+impl Default for DmgCompression {
+    /// Defaults to the smallest-output setting (`zlib` level 9), matching
+    /// the previous hard-coded `UDZO` behavior while actually compressing
+    /// as much as the format allows.
+    fn default() -> Self {
+        DmgCompression::Zlib { level: 9 }
+    }
+}
+
+impl DmgCompression {
+    /// Returns the `hdiutil convert` arguments for this setting, e.g.
+    /// `["-format", "UDZO", "-imagekey", "zlib-level=9"]`.
+    fn hdiutil_args(&self) -> Vec<String> {
+        match self {
+            DmgCompression::Zlib { level } => vec![
+                "-format".into(),
+                "UDZO".into(),
+                "-imagekey".into(),
+                format!("zlib-level={}", (*level).clamp(1, 9)),
+            ],
+            DmgCompression::Bzip2 => vec!["-format".into(), "UDBZ".into()],
+            DmgCompression::Lzfse => vec!["-format".into(), "ULFO".into()],
+            DmgCompression::Lzma => vec!["-format".into(), "ULMO".into()],
+            DmgCompression::None => vec!["-format".into(), "UDRO".into()],
+        }
+    }
+}
+
+impl DmgConfig {
+    /// Load a DMG spec from a `.json`, `.toml`, or `.yaml`/`.yml` file,
+    /// resolving the `path` of each content entry plus `icon` and
+    /// `background` against the spec file's parent directory so the spec
+    /// stays portable when moved alongside its assets.
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Result<DmgConfig> {
+        let path = path.as_ref();
+        let raw = std::fs::read_to_string(path)
+            .with_context(|| format!("failed to read DMG spec {:?}", path))?;
+
+        let ext = path.extension().and_then(|e| e.to_str()).unwrap_or("");
+        let mut config: DmgConfig = match ext {
+            "json" => serde_json::from_str(&raw)
+                .with_context(|| format!("failed to parse {:?} as JSON", path))?,
+            "toml" => toml::from_str(&raw)
+                .with_context(|| format!("failed to parse {:?} as TOML", path))?,
+            "yaml" | "yml" => serde_yaml::from_str(&raw)
+                .with_context(|| format!("failed to parse {:?} as YAML", path))?,
+            other => return Err(anyhow::anyhow!("unsupported DMG spec extension: {:?}", other)),
+        };
+
+        let base = path.parent().unwrap_or_else(|| Path::new("."));
+        config.icon = resolve_relative(base, &config.icon);
+        config.background = resolve_relative(base, &config.background);
+        for item in &mut config.contents {
+            item.path = resolve_relative(base, &item.path);
+        }
+
+        Ok(config)
+    }
+}
+
+/// Joins `candidate` onto `base` unless it is already absolute, so spec
+/// files can reference assets relative to their own location. An empty
+/// `candidate` is left untouched (rather than resolving to `base` itself),
+/// since `""` is how a spec opts out of an optional `icon`/`background`.
+fn resolve_relative(base: &Path, candidate: &str) -> String {
+    let candidate_path = Path::new(candidate);
+    if candidate.is_empty() {
+        String::new()
+    } else if candidate_path.is_absolute() {
+        candidate.to_string()
+    } else {
+        let joined: PathBuf = base.join(candidate_path);
+        joined.to_string_lossy().into_owned()
+    }
+}
+
+// ---------------------------
+// Progress Reporting
+// ---------------------------
+
+/// A stage reported through the `tx` channel passed to
+/// [`build_with_progress`], in the order `build` executes them.
+#[derive(Debug, Clone)]
+pub enum BuildProgress {
+    CopyingContents { done: usize, total: usize },
+    CreatingImage,
+    Attaching,
+    WritingLayout,
+    Detaching,
+    Converting,
+    Done,
+}
+
+/// Sends `progress` if a sender was supplied, ignoring a closed receiver
+/// (the caller may have dropped interest in progress updates). Awaits the
+/// send so a full channel backpressures the build rather than dropping
+/// events — including the terminal `Done` event a progress-bar UI relies
+/// on to know the build finished.
+async fn report(tx: Option<&tokio::sync::mpsc::Sender<BuildProgress>>, progress: BuildProgress) {
+    if let Some(tx) = tx {
+        let _ = tx.send(progress).await;
+    }
 }
 
 // ---------------------------
@@ -55,26 +206,51 @@ pub struct DmgConfig {
 
 /// Create a DMG file based on the provided configuration.
 pub async fn build(config: &DmgConfig, final_dmg_path: &Path) -> Result<()> {
+    build_impl(config, final_dmg_path, None).await
+}
+
+/// Create a DMG file, reporting [`BuildProgress`] events through `tx` as
+/// each stage of the pipeline runs, so GUI/CLI front-ends can render a
+/// progress bar instead of a frozen terminal.
+pub async fn build_with_progress(
+    config: &DmgConfig,
+    final_dmg_path: &Path,
+    tx: tokio::sync::mpsc::Sender<BuildProgress>,
+) -> Result<()> {
+    build_impl(config, final_dmg_path, Some(&tx)).await
+}
+
+async fn build_impl(
+    config: &DmgConfig,
+    final_dmg_path: &Path,
+    tx: Option<&tokio::sync::mpsc::Sender<BuildProgress>>,
+) -> Result<()> {
     // 1. Prepare temp directory
     let temp_dir = std::env::temp_dir().join(format!("appdmg_rs_{}", std::process::id()));
     if temp_dir.exists() { fs::remove_dir_all(&temp_dir).await?; }
     fs::create_dir_all(&temp_dir).await?;
 
     // 2. Copy contents
-    for item in &config.contents {
+    let total_items = config.contents.len();
+    let copy_workers = config.copy_workers
+        .unwrap_or_else(|| std::thread::available_parallelism().map(|n| n.get()).unwrap_or(4));
+    report(tx, BuildProgress::CopyingContents { done: 0, total: total_items }).await;
+    for (done, item) in config.contents.iter().enumerate() {
         let src_path = Path::new(&item.path);
         let item_name = item.name.as_deref().or_else(|| src_path.file_name().and_then(|n| n.to_str())).unwrap_or("file");
         let dest_path = temp_dir.join(item_name);
 
         if item.type_ == "file" {
-             let status = Command::new("cp").arg("-R").arg(src_path).arg(&dest_path).status()?;
-             if !status.success() { return Err(anyhow::anyhow!("Failed to copy content: {:?}", src_path)); }
+             copy::copy_tree_parallel(src_path, &dest_path, copy_workers, config.verify_copies).await
+                 .with_context(|| format!("failed to copy content: {:?}", src_path))?;
         } else if item.type_ == "link" {
              let _ = tokio::fs::symlink(src_path, &dest_path).await;
         }
+        report(tx, BuildProgress::CopyingContents { done: done + 1, total: total_items }).await;
     }
 
     // 3. Create HFS+ DMG
+    report(tx, BuildProgress::CreatingImage).await;
     let temp_dmg_path = temp_dir.parent().unwrap().join(format!("temp_rw_{}.dmg", std::process::id()));
     if temp_dmg_path.exists() { fs::remove_file(&temp_dmg_path).await?; }
 
@@ -89,20 +265,24 @@ pub async fn build(config: &DmgConfig, final_dmg_path: &Path) -> Result<()> {
     if !status.success() { return Err(anyhow::anyhow!("hdiutil create failed")); }
 
     // 4. Attach
+    report(tx, BuildProgress::Attaching).await;
     let attach_out = Command::new("hdiutil").arg("attach").arg("-readwrite").arg("-noverify").arg("-noautoopen").arg(&temp_dmg_path).output()?;
     let out_str = String::from_utf8_lossy(&attach_out.stdout);
     let mount_point = out_str.lines().find_map(|l| l.split('\t').last().map(|s| s.trim()).filter(|s| s.starts_with("/Volumes/"))).ok_or_else(|| anyhow::anyhow!("No mount point"))?;
     let mount_path = Path::new(mount_point);
 
     // 5. Layout
+    report(tx, BuildProgress::WritingLayout).await;
     // Background Setup
     let bg_dir = mount_path.join(".background");
     fs::create_dir_all(&bg_dir).await?;
     let bg_src = Path::new(&config.background);
-    let vol_bg_path = bg_dir.join("background.png");
-    if bg_src.exists() {
-        fs::copy(bg_src, &vol_bg_path).await?;
-    }
+    let vol_bg_path = if bg_src.exists() {
+        let window_size = (config.window.size.width, config.window.size.height);
+        background::prepare_background(bg_src, window_size, &bg_dir)?.alias_target().to_path_buf()
+    } else {
+        bg_dir.join("background.png")
+    };
     let _ = Command::new("chflags").arg("hidden").arg(&bg_dir).status();
     let _ = Command::new("chflags").arg("hidden").arg(mount_path.join(".fseventsd")).status();
 
@@ -125,25 +305,171 @@ pub async fn build(config: &DmgConfig, final_dmg_path: &Path) -> Result<()> {
     write_ds_store(&mount_path.join(".DS_Store"), entries).await?;
 
     // Volume Icon
-    if Path::new(&config.icon).exists() {
+    let icon_src = Path::new(&config.icon);
+    if icon_src.exists() {
+         let icns_src = icon::ensure_icns(icon_src, &temp_dir)?;
          let dest_icon = mount_path.join(".VolumeIcon.icns");
-         if let Ok(_) = fs::copy(&config.icon, &dest_icon).await {
-             let _ = Command::new("chflags").arg("hidden").arg(&dest_icon).status();
-             let _ = Command::new("SetFile").arg("-a").arg("C").arg(mount_path).status();
-         }
+         fs::copy(&icns_src, &dest_icon).await
+             .with_context(|| format!("failed to install volume icon {:?}", dest_icon))?;
+         let _ = Command::new("chflags").arg("hidden").arg(&dest_icon).status();
+         let _ = Command::new("SetFile").arg("-a").arg("C").arg(mount_path).status();
     }
     
     let _ = Command::new("sync").status();
 
     // 6. Detach & Convert
+    report(tx, BuildProgress::Detaching).await;
     Command::new("hdiutil").arg("detach").arg(mount_point).arg("-force").arg("-quiet").status()?;
-    
+
     if final_dmg_path.exists() { fs::remove_file(final_dmg_path).await?; }
-    let status = Command::new("hdiutil").arg("convert").arg(&temp_dmg_path).arg("-format").arg("UDZO").arg("-o").arg(final_dmg_path).arg("-quiet").status()?;
-    
+    report(tx, BuildProgress::Converting).await;
+    let status = Command::new("hdiutil")
+        .arg("convert").arg(&temp_dmg_path)
+        .args(config.compression.hdiutil_args())
+        .arg("-o").arg(final_dmg_path)
+        .arg("-quiet")
+        .status()?;
+
     let _ = fs::remove_dir_all(&temp_dir).await;
     let _ = fs::remove_file(&temp_dmg_path).await;
-    
+
     if !status.success() { return Err(anyhow::anyhow!("hdiutil convert failed")); }
+    report(tx, BuildProgress::Done).await;
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn zlib_args_clamp_level_to_valid_range() {
+        let too_high = DmgCompression::Zlib { level: 20 };
+        assert_eq!(too_high.hdiutil_args(), vec!["-format", "UDZO", "-imagekey", "zlib-level=9"]);
+
+        let too_low = DmgCompression::Zlib { level: 0 };
+        assert_eq!(too_low.hdiutil_args(), vec!["-format", "UDZO", "-imagekey", "zlib-level=1"]);
+    }
+
+    #[test]
+    fn compression_formats_map_to_expected_hdiutil_flags() {
+        assert_eq!(DmgCompression::Bzip2.hdiutil_args(), vec!["-format", "UDBZ"]);
+        assert_eq!(DmgCompression::Lzfse.hdiutil_args(), vec!["-format", "ULFO"]);
+        assert_eq!(DmgCompression::Lzma.hdiutil_args(), vec!["-format", "ULMO"]);
+        assert_eq!(DmgCompression::None.hdiutil_args(), vec!["-format", "UDRO"]);
+    }
+
+    #[test]
+    fn default_compression_is_zlib_level_nine() {
+        assert_eq!(DmgCompression::default().hdiutil_args(), vec!["-format", "UDZO", "-imagekey", "zlib-level=9"]);
+    }
+
+    #[test]
+    fn resolve_relative_joins_onto_base_dir() {
+        let base = Path::new("/specs/myapp");
+        assert_eq!(resolve_relative(base, "assets/icon.png"), "/specs/myapp/assets/icon.png");
+    }
+
+    #[test]
+    fn resolve_relative_leaves_absolute_paths_untouched() {
+        let base = Path::new("/specs/myapp");
+        assert_eq!(resolve_relative(base, "/opt/shared/icon.png"), "/opt/shared/icon.png");
+    }
+
+    #[test]
+    fn resolve_relative_leaves_empty_candidate_empty() {
+        let base = Path::new("/specs/myapp");
+        assert_eq!(resolve_relative(base, ""), "");
+    }
+
+    /// Creates a fresh, empty scratch directory under the OS temp dir.
+    fn scratch_dir(label: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("appdmg_lib_test_{}_{}", label, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    /// Asserts the fields `from_path` should apply identically regardless
+    /// of source format: path resolution against the spec's own directory,
+    /// and defaults for the fields the fixture spec omits.
+    fn assert_parsed_as_expected(config: DmgConfig, dir: &Path) {
+        assert_eq!(config.title, "My App");
+        assert_eq!(config.icon, dir.join("icon.png").to_string_lossy());
+        assert_eq!(config.background, dir.join("background.png").to_string_lossy());
+        assert_eq!(config.contents[0].path, dir.join("MyApp.app").to_string_lossy());
+        assert_eq!(config.icon_size, 128.0);
+        assert_eq!(config.window.size.width, 540);
+        assert_eq!(config.window.size.height, 380);
+        assert!(config.verify_copies);
+        assert!(matches!(config.compression, DmgCompression::Zlib { level: 9 }));
+    }
+
+    #[test]
+    fn from_path_parses_json_spec_and_resolves_paths() {
+        let dir = scratch_dir("json");
+        let spec_path = dir.join("spec.json");
+        std::fs::write(&spec_path, r#"{
+            "title": "My App",
+            "icon": "icon.png",
+            "background": "background.png",
+            "contents": [{"x": 0, "y": 0, "type": "file", "path": "MyApp.app"}]
+        }"#).unwrap();
+
+        let config = DmgConfig::from_path(&spec_path).unwrap();
+        assert_parsed_as_expected(config, &dir);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn from_path_parses_toml_spec_and_resolves_paths() {
+        let dir = scratch_dir("toml");
+        let spec_path = dir.join("spec.toml");
+        std::fs::write(&spec_path, r#"
+            title = "My App"
+            icon = "icon.png"
+            background = "background.png"
+
+            [[contents]]
+            x = 0
+            y = 0
+            type = "file"
+            path = "MyApp.app"
+        "#).unwrap();
+
+        let config = DmgConfig::from_path(&spec_path).unwrap();
+        assert_parsed_as_expected(config, &dir);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn from_path_parses_yaml_spec_and_resolves_paths() {
+        let dir = scratch_dir("yaml");
+        let spec_path = dir.join("spec.yaml");
+        std::fs::write(&spec_path, r#"
+title: My App
+icon: icon.png
+background: background.png
+contents:
+  - x: 0
+    y: 0
+    type: file
+    path: MyApp.app
+"#).unwrap();
+
+        let config = DmgConfig::from_path(&spec_path).unwrap();
+        assert_parsed_as_expected(config, &dir);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn from_path_rejects_unsupported_extension() {
+        let dir = scratch_dir("bad-ext");
+        let spec_path = dir.join("spec.ini");
+        std::fs::write(&spec_path, "title=My App").unwrap();
+
+        let err = DmgConfig::from_path(&spec_path).unwrap_err();
+        assert!(err.to_string().contains("unsupported DMG spec extension"));
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}